@@ -0,0 +1,361 @@
+use anyhow::{Context as _, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+
+use crate::EvaluationQueryOutcome;
+
+/// Persists the results of `cargo run -p evals -- run` invocations so that
+/// quality can be tracked run-over-run instead of only read off stdout.
+pub struct EvalDb {
+    conn: Connection,
+}
+
+pub struct StoredQueryOutcome {
+    pub repo: String,
+    pub sha: String,
+    pub query: String,
+    pub covered_result_count: usize,
+    pub total_result_count: usize,
+}
+
+/// A query whose coverage ratio dropped between the baseline and new run,
+/// kept distinct from `newly_failed` (which only tracks full pass -> fail
+/// transitions) so notifiers can report the actual old/new coverage.
+pub struct QueryRegression {
+    pub repo: String,
+    pub query: String,
+    pub baseline_covered_result_count: usize,
+    pub baseline_total_result_count: usize,
+    pub new_covered_result_count: usize,
+    pub new_total_result_count: usize,
+}
+
+impl QueryRegression {
+    pub fn baseline_coverage(&self) -> f64 {
+        coverage_ratio(
+            self.baseline_covered_result_count,
+            self.baseline_total_result_count,
+        )
+    }
+
+    pub fn new_coverage(&self) -> f64 {
+        coverage_ratio(self.new_covered_result_count, self.new_total_result_count)
+    }
+}
+
+/// A repo whose coverage dropped to zero in the new run after having some
+/// coverage in the baseline.
+pub struct RepoRegression {
+    pub repo: String,
+    pub baseline_covered_result_count: usize,
+    pub baseline_total_result_count: usize,
+}
+
+pub struct RunComparison {
+    pub baseline_run_id: i64,
+    pub new_run_id: i64,
+    pub baseline_covered_result_count: usize,
+    pub baseline_total_result_count: usize,
+    pub baseline_mean_ndcg: f64,
+    pub baseline_mrr: f64,
+    pub new_covered_result_count: usize,
+    pub new_total_result_count: usize,
+    pub new_mean_ndcg: f64,
+    pub new_mrr: f64,
+    pub newly_passed: Vec<StoredQueryOutcome>,
+    pub newly_failed: Vec<StoredQueryOutcome>,
+    pub query_regressions: Vec<QueryRegression>,
+    pub zeroed_out_repos: Vec<RepoRegression>,
+}
+
+impl RunComparison {
+    pub fn baseline_coverage(&self) -> f64 {
+        coverage_ratio(
+            self.baseline_covered_result_count,
+            self.baseline_total_result_count,
+        )
+    }
+
+    pub fn new_coverage(&self) -> f64 {
+        coverage_ratio(self.new_covered_result_count, self.new_total_result_count)
+    }
+
+    pub fn coverage_delta(&self) -> f64 {
+        self.new_coverage() - self.baseline_coverage()
+    }
+
+    pub fn ndcg_delta(&self) -> f64 {
+        self.new_mean_ndcg - self.baseline_mean_ndcg
+    }
+
+    pub fn mrr_delta(&self) -> f64 {
+        self.new_mrr - self.baseline_mrr
+    }
+
+    pub fn has_regression(&self) -> bool {
+        !self.newly_failed.is_empty()
+    }
+}
+
+fn coverage_ratio(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.
+    } else {
+        covered as f64 / total as f64
+    }
+}
+
+fn sum_coverage(outcomes: &[StoredQueryOutcome], repo: &str) -> (usize, usize) {
+    outcomes.iter().filter(|outcome| outcome.repo == repo).fold(
+        (0, 0),
+        |(covered, total), outcome| {
+            (
+                covered + outcome.covered_result_count,
+                total + outcome.total_result_count,
+            )
+        },
+    )
+}
+
+impl EvalDb {
+    pub fn open(db_path: &Path) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent).context("failed to create eval db directory")?;
+        }
+        let conn = Connection::open(db_path).context("failed to open eval db")?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                embedding_model TEXT NOT NULL,
+                git_sha TEXT NOT NULL,
+                covered_result_count INTEGER NOT NULL,
+                total_result_count INTEGER NOT NULL,
+                mean_ndcg REAL NOT NULL DEFAULT 0,
+                mrr REAL NOT NULL DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS query_outcomes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                run_id INTEGER NOT NULL REFERENCES runs (id),
+                repo TEXT NOT NULL,
+                sha TEXT NOT NULL,
+                query TEXT NOT NULL,
+                covered_result_count INTEGER NOT NULL,
+                total_result_count INTEGER NOT NULL,
+                expected_results TEXT NOT NULL,
+                actual_results TEXT NOT NULL
+            );
+            ",
+        )
+        .context("failed to create eval db schema")?;
+        Ok(Self { conn })
+    }
+
+    /// Inserts a new, not-yet-finished run and returns its id.
+    pub fn start_run(&self, embedding_model: &str, git_sha: &str) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO runs (timestamp, embedding_model, git_sha, covered_result_count, total_result_count, mean_ndcg, mrr)
+             VALUES (datetime('now'), ?1, ?2, 0, 0, 0, 0)",
+            params![embedding_model, git_sha],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn record_query_outcome(
+        &self,
+        run_id: i64,
+        repo: &str,
+        sha: &str,
+        outcome: &EvaluationQueryOutcome,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO query_outcomes
+                (run_id, repo, sha, query, covered_result_count, total_result_count, expected_results, actual_results)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                run_id,
+                repo,
+                sha,
+                outcome.query,
+                outcome.covered_result_count as i64,
+                outcome.total_result_count as i64,
+                serde_json::to_string(&outcome.expected_results)?,
+                serde_json::to_string(&outcome.actual_results)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn finish_run(
+        &self,
+        run_id: i64,
+        covered_result_count: usize,
+        total_result_count: usize,
+        mean_ndcg: f64,
+        mrr: f64,
+    ) -> Result<()> {
+        self.conn.execute(
+            "UPDATE runs SET covered_result_count = ?1, total_result_count = ?2, mean_ndcg = ?3, mrr = ?4 WHERE id = ?5",
+            params![
+                covered_result_count as i64,
+                total_result_count as i64,
+                mean_ndcg,
+                mrr,
+                run_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the id of the most recently started run, excluding `exclude_run_id`.
+    /// Runs that were started but never finished are not skipped.
+    pub fn latest_run_id(&self, exclude_run_id: i64) -> Result<Option<i64>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id FROM runs WHERE id != ?1 ORDER BY id DESC LIMIT 1",
+                params![exclude_run_id],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn query_outcomes_for_run(&self, run_id: i64) -> Result<Vec<StoredQueryOutcome>> {
+        let mut statement = self.conn.prepare(
+            "SELECT repo, sha, query, covered_result_count, total_result_count
+             FROM query_outcomes WHERE run_id = ?1",
+        )?;
+        let rows = statement
+            .query_map(params![run_id], |row| {
+                Ok(StoredQueryOutcome {
+                    repo: row.get(0)?,
+                    sha: row.get(1)?,
+                    query: row.get(2)?,
+                    covered_result_count: row.get::<_, i64>(3)? as usize,
+                    total_result_count: row.get::<_, i64>(4)? as usize,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Compares the per-query coverage of `new_run_id` against `baseline_run_id`,
+    /// reporting which queries newly passed, which newly failed (regressed), and
+    /// the net delta in aggregate coverage.
+    pub fn compare_runs(&self, baseline_run_id: i64, new_run_id: i64) -> Result<RunComparison> {
+        let baseline_run = self.conn.query_row(
+            "SELECT covered_result_count, total_result_count, mean_ndcg, mrr FROM runs WHERE id = ?1",
+            params![baseline_run_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                ))
+            },
+        )?;
+        let new_run = self.conn.query_row(
+            "SELECT covered_result_count, total_result_count, mean_ndcg, mrr FROM runs WHERE id = ?1",
+            params![new_run_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                ))
+            },
+        )?;
+
+        let baseline_outcomes = self.query_outcomes_for_run(baseline_run_id)?;
+        let new_outcomes = self.query_outcomes_for_run(new_run_id)?;
+
+        let was_covered = |outcome: &StoredQueryOutcome| {
+            outcome.total_result_count > 0
+                && outcome.covered_result_count == outcome.total_result_count
+        };
+        let coverage_of = |outcome: &StoredQueryOutcome| {
+            coverage_ratio(outcome.covered_result_count, outcome.total_result_count)
+        };
+
+        let mut newly_passed = Vec::new();
+        let mut newly_failed = Vec::new();
+        let mut query_regressions = Vec::new();
+        for new_outcome in new_outcomes {
+            let baseline_outcome = baseline_outcomes.iter().find(|o| {
+                o.repo == new_outcome.repo
+                    && o.sha == new_outcome.sha
+                    && o.query == new_outcome.query
+            });
+
+            let was_covered_before = baseline_outcome.map_or(false, was_covered);
+            let is_covered_now = was_covered(&new_outcome);
+
+            if is_covered_now && !was_covered_before {
+                newly_passed.push(new_outcome);
+                continue;
+            }
+            if was_covered_before && !is_covered_now {
+                newly_failed.push(StoredQueryOutcome {
+                    repo: new_outcome.repo.clone(),
+                    sha: new_outcome.sha.clone(),
+                    query: new_outcome.query.clone(),
+                    covered_result_count: new_outcome.covered_result_count,
+                    total_result_count: new_outcome.total_result_count,
+                });
+            }
+
+            if let Some(baseline_outcome) = baseline_outcome {
+                if coverage_of(&new_outcome) < coverage_of(baseline_outcome) {
+                    query_regressions.push(QueryRegression {
+                        repo: new_outcome.repo.clone(),
+                        query: new_outcome.query.clone(),
+                        baseline_covered_result_count: baseline_outcome.covered_result_count,
+                        baseline_total_result_count: baseline_outcome.total_result_count,
+                        new_covered_result_count: new_outcome.covered_result_count,
+                        new_total_result_count: new_outcome.total_result_count,
+                    });
+                }
+            }
+        }
+
+        let zeroed_out_repos = baseline_outcomes
+            .iter()
+            .map(|outcome| &outcome.repo)
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|repo| {
+                let (baseline_covered, baseline_total) = sum_coverage(&baseline_outcomes, repo);
+                let (new_covered, new_total) = sum_coverage(&new_outcomes, repo);
+                if baseline_total > 0 && baseline_covered > 0 && new_total > 0 && new_covered == 0 {
+                    Some(RepoRegression {
+                        repo: repo.clone(),
+                        baseline_covered_result_count: baseline_covered,
+                        baseline_total_result_count: baseline_total,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(RunComparison {
+            baseline_run_id,
+            new_run_id,
+            baseline_covered_result_count: baseline_run.0 as usize,
+            baseline_total_result_count: baseline_run.1 as usize,
+            baseline_mean_ndcg: baseline_run.2,
+            baseline_mrr: baseline_run.3,
+            new_covered_result_count: new_run.0 as usize,
+            new_total_result_count: new_run.1 as usize,
+            new_mean_ndcg: new_run.2,
+            new_mrr: new_run.3,
+            newly_passed,
+            newly_failed,
+            query_regressions,
+            zeroed_out_repos,
+        })
+    }
+}
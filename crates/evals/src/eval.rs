@@ -1,13 +1,21 @@
+mod db;
+mod metrics;
+mod notifier;
+mod progress;
+mod server;
+mod webhook;
+
 use ::fs::{Fs, RealFs};
 use anyhow::Result;
 use clap::Parser;
 use client::{Client, UserStore};
 use clock::RealSystemClock;
 use collections::BTreeMap;
-use futures::channel::oneshot;
+use db::EvalDb;
+use futures::{channel::oneshot, StreamExt as _};
 use git::GitHostingProviderRegistry;
 use gpui::{AsyncAppContext, BackgroundExecutor, Context, Model, Task};
-use http_client::{HttpClient, Method};
+use http_client::HttpClient;
 use language::LanguageRegistry;
 use node_runtime::FakeNodeRuntime;
 use open_ai::OpenAiEmbeddingModel;
@@ -18,9 +26,10 @@ use settings::SettingsStore;
 use smol::io::AsyncReadExt;
 use std::{
     fs,
+    net::SocketAddr,
     ops::Range,
-    path::Path,
-    process::{exit, Command, Stdio},
+    path::{Path, PathBuf},
+    process::{exit, Command},
     sync::{
         atomic::{AtomicUsize, Ordering::SeqCst},
         Arc,
@@ -30,6 +39,7 @@ use std::{
 const CODESEARCH_NET_DIR: &'static str = "target/datasets/code-search-net";
 const EVAL_REPOS_DIR: &'static str = "target/datasets/eval-repos";
 const EVAL_DB_PATH: &'static str = "target/eval_db";
+const EVAL_DB_RESULTS_PATH: &'static str = "target/eval_results.db";
 const SEARCH_RESULT_LIMIT: usize = 8;
 const SKIP_EVAL_PATH: &'static str = ".skip_eval";
 
@@ -43,7 +53,40 @@ struct Cli {
 #[derive(clap::Subcommand)]
 enum Commands {
     Fetch {},
-    Run {},
+    Run {
+        /// Diff this run's per-query coverage against a specific prior run id,
+        /// printing newly-passed/newly-failed queries and the net coverage delta.
+        #[arg(long)]
+        baseline: Option<i64>,
+
+        /// Like `--baseline`, but compares against the most recent prior run
+        /// instead of a specific run id.
+        #[arg(long)]
+        compare_latest: bool,
+
+        /// Path to a JSON or TOML file configuring where to send regression
+        /// notifications (email and/or webhook). No notification is sent,
+        /// even on a regression, unless this is set.
+        #[arg(long)]
+        notify_config: Option<PathBuf>,
+
+        /// Address to stream live progress events (one JSON object per
+        /// line) from, over a `/progress` HTTP endpoint. Unset by default,
+        /// since the `\r`-rewritten stderr line is enough for an attached
+        /// TTY.
+        #[arg(long)]
+        progress_addr: Option<SocketAddr>,
+    },
+    Serve {
+        /// Path to a JSON file listing the pre-shared keys GitHub may have
+        /// signed a webhook delivery with.
+        #[arg(long)]
+        webhook_config: PathBuf,
+
+        /// Address to listen for GitHub webhook deliveries on.
+        #[arg(long, default_value = "0.0.0.0:8080")]
+        addr: SocketAddr,
+    },
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -63,6 +106,12 @@ struct EvaluationQuery {
 struct EvaluationSearchResult {
     file: String,
     lines: Range<u32>,
+    /// The CodeSearchNet-annotated relevance of this span to its query
+    /// (typically 0-3). Zero for actual search results, which carry no
+    /// annotation of their own and are only graded by matching an expected
+    /// span's relevance.
+    #[serde(default)]
+    relevance: u8,
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -79,6 +128,8 @@ struct EvaluationQueryOutcome {
     actual_results: Vec<EvaluationSearchResult>,
     covered_result_count: usize,
     total_result_count: usize,
+    #[serde(flatten)]
+    metrics: metrics::QueryMetrics,
 }
 
 fn main() -> Result<()> {
@@ -101,9 +152,38 @@ fn main() -> Result<()> {
                     })
                     .detach();
             }
-            Commands::Run {} => {
+            Commands::Run {
+                baseline,
+                compare_latest,
+                notify_config,
+                progress_addr,
+            } => {
                 cx.spawn(|mut cx| async move {
-                    if let Err(err) = run_evaluation(&executor, &mut cx).await {
+                    match run_evaluation(
+                        &executor,
+                        &mut cx,
+                        baseline,
+                        compare_latest,
+                        notify_config,
+                        progress_addr,
+                    )
+                    .await
+                    {
+                        Ok(regressed) => exit(regressed as i32),
+                        Err(err) => {
+                            eprintln!("Error: {}", err);
+                            exit(1);
+                        }
+                    }
+                })
+                .detach();
+            }
+            Commands::Serve {
+                webhook_config,
+                addr,
+            } => {
+                cx.spawn(|mut cx| async move {
+                    if let Err(err) = serve(&executor, &mut cx, webhook_config, addr).await {
                         eprintln!("Error: {}", err);
                         exit(1);
                     }
@@ -158,8 +238,15 @@ async fn fetch_code_search_net_resources(http_client: &dyn HttpClient) -> Result
         let query = values.next()?;
         let github_url = values.next()?;
         let score = values.next()?;
+        let relevance: u8 = match score.parse() {
+            Ok(relevance) => relevance,
+            Err(_) => {
+                eprintln!("skipping annotation with unparseable relevance score {score:?}");
+                return None;
+            }
+        };
 
-        if score == "0" {
+        if relevance == 0 {
             return None;
         }
 
@@ -173,12 +260,12 @@ async fn fetch_code_search_net_resources(http_client: &dyn HttpClient) -> Result
             let row = hash.strip_prefix("L")?.parse().ok()?;
             row..row + 1
         };
-        Some((repo_name, sha, query, file_path, line_range))
+        Some((repo_name, sha, query, file_path, line_range, relevance))
     });
 
     // Group the annotations by repo and sha.
     let mut evaluations_by_repo = BTreeMap::new();
-    for (repo_name, sha, query, file_path, lines) in rows {
+    for (repo_name, sha, query, file_path, lines, relevance) in rows {
         let evaluation_project = evaluations_by_repo
             .entry((repo_name, sha))
             .or_insert_with(|| EvaluationProject {
@@ -202,6 +289,7 @@ async fn fetch_code_search_net_resources(http_client: &dyn HttpClient) -> Result
         let result = EvaluationSearchResult {
             file: file_path.to_string(),
             lines,
+            relevance,
         };
         if !results.contains(&result) {
             results.push(result);
@@ -224,7 +312,21 @@ async fn fetch_code_search_net_resources(http_client: &dyn HttpClient) -> Result
     Ok(())
 }
 
-async fn run_evaluation(executor: &BackgroundExecutor, cx: &mut AsyncAppContext) -> Result<()> {
+/// The parts of the eval harness's setup that are shared between a one-shot
+/// `Run` and the job processing loop behind `Serve`.
+struct EvalContext {
+    client: Arc<Client>,
+    user_store: Model<UserStore>,
+    node_runtime: Arc<FakeNodeRuntime>,
+    language_registry: Arc<LanguageRegistry>,
+    fs: Arc<dyn Fs>,
+    repos_dir: PathBuf,
+}
+
+async fn init_eval_context(
+    executor: &BackgroundExecutor,
+    cx: &mut AsyncAppContext,
+) -> Result<EvalContext> {
     cx.update(|cx| {
         let mut store = SettingsStore::new(cx);
         store
@@ -237,12 +339,6 @@ async fn run_evaluation(executor: &BackgroundExecutor, cx: &mut AsyncAppContext)
     })
     .unwrap();
 
-    let dataset_dir = Path::new(CODESEARCH_NET_DIR);
-    let evaluations_path = dataset_dir.join("evaluations.json");
-    let repos_dir = Path::new(EVAL_REPOS_DIR);
-    let db_path = Path::new(EVAL_DB_PATH);
-    let http_client = http_client::HttpClientWithProxy::new(None, None);
-    let api_key = std::env::var("OPENAI_API_KEY").unwrap();
     let git_hosting_provider_registry = Arc::new(GitHostingProviderRegistry::new());
     let fs = Arc::new(RealFs::new(git_hosting_provider_registry, None)) as Arc<dyn Fs>;
     let clock = Arc::new(RealSystemClock);
@@ -263,6 +359,161 @@ async fn run_evaluation(executor: &BackgroundExecutor, cx: &mut AsyncAppContext)
         .new_model(|cx| UserStore::new(client.clone(), cx))
         .unwrap();
     let node_runtime = Arc::new(FakeNodeRuntime {});
+    let language_registry = Arc::new(LanguageRegistry::new(Task::ready(()), executor.clone()));
+    cx.update(|cx| languages::init(language_registry.clone(), node_runtime.clone(), cx))
+        .unwrap();
+
+    Ok(EvalContext {
+        client,
+        user_store,
+        node_runtime,
+        language_registry,
+        fs,
+        repos_dir: EVAL_REPOS_DIR.into(),
+    })
+}
+
+/// Indexes `evaluation_project` and runs each of its queries against the
+/// resulting `ProjectIndex`, returning the per-query outcomes.
+async fn evaluate_project(
+    ctx: &EvalContext,
+    semantic_index: &mut SemanticIndex,
+    evaluation_project: EvaluationProject,
+    cx: &mut AsyncAppContext,
+    progress: &progress::ProgressReporter,
+) -> Result<EvaluationProjectOutcome> {
+    progress.emit(progress::ProgressEvent::RepoStarted {
+        repo: evaluation_project.repo.clone(),
+    });
+
+    let repo_dir = ctx.repos_dir.join(&evaluation_project.repo);
+
+    let project = cx
+        .update(|cx| {
+            Project::local(
+                ctx.client.clone(),
+                ctx.node_runtime.clone(),
+                ctx.user_store.clone(),
+                ctx.language_registry.clone(),
+                ctx.fs.clone(),
+                None,
+                cx,
+            )
+        })
+        .unwrap();
+
+    let (worktree, _) = project
+        .update(cx, |project, cx| {
+            project.find_or_create_worktree(repo_dir, true, cx)
+        })?
+        .await?;
+
+    worktree
+        .update(cx, |worktree, _| {
+            worktree.as_local().unwrap().scan_complete()
+        })
+        .unwrap()
+        .await;
+
+    let project_index = cx
+        .update(|cx| semantic_index.project_index(project.clone(), cx))
+        .unwrap();
+
+    wait_for_indexing_complete(&project_index, cx, progress, &evaluation_project.repo).await;
+
+    let mut queries = Vec::new();
+    let mut running_covered_result_count = 0;
+    let mut running_total_result_count = 0;
+    for query in evaluation_project.queries {
+        let results = cx
+            .update(|cx| {
+                let project_index = project_index.read(cx);
+                project_index.search(query.query.clone(), SEARCH_RESULT_LIMIT, cx)
+            })
+            .unwrap()
+            .await
+            .unwrap();
+
+        let results = SemanticIndex::load_results(results, &ctx.fs.clone(), &cx)
+            .await
+            .unwrap();
+
+        let mut project_covered_result_count = 0;
+        for expected_result in &query.expected_results {
+            let was_covered = results.iter().any(|result| {
+                result.path.as_ref() == Path::new(&expected_result.file)
+                    && result.row_range.contains(&expected_result.lines.start)
+                    && result.row_range.contains(&expected_result.lines.end)
+            });
+            if was_covered {
+                project_covered_result_count += 1
+            };
+        }
+
+        let actual_results = results
+            .iter()
+            .map(|result| EvaluationSearchResult {
+                file: result.path.to_string_lossy().to_string(),
+                lines: result.row_range.clone(),
+                relevance: 0,
+            })
+            .collect::<Vec<_>>();
+        let metrics = metrics::compute(
+            &query.expected_results,
+            &actual_results,
+            SEARCH_RESULT_LIMIT,
+        );
+
+        running_covered_result_count += project_covered_result_count;
+        running_total_result_count += query.expected_results.len();
+        progress.emit(progress::ProgressEvent::QueryCompleted {
+            repo: evaluation_project.repo.clone(),
+            query: query.query.clone(),
+            covered_result_count: running_covered_result_count,
+            total_result_count: running_total_result_count,
+        });
+
+        queries.push(EvaluationQueryOutcome {
+            query: query.query,
+            total_result_count: query.expected_results.len(),
+            covered_result_count: project_covered_result_count,
+            expected_results: query.expected_results,
+            actual_results,
+            metrics,
+        });
+    }
+
+    Ok(EvaluationProjectOutcome {
+        repo: evaluation_project.repo,
+        sha: evaluation_project.sha,
+        queries,
+    })
+}
+
+/// Runs the eval suite, persisting the outcome to the eval db. Returns `true`
+/// if a baseline comparison was requested and a regression was found, so the
+/// caller can fail the process (e.g. in CI).
+async fn run_evaluation(
+    executor: &BackgroundExecutor,
+    cx: &mut AsyncAppContext,
+    baseline: Option<i64>,
+    compare_latest: bool,
+    notify_config: Option<PathBuf>,
+    progress_addr: Option<SocketAddr>,
+) -> Result<bool> {
+    let ctx = init_eval_context(executor, cx).await?;
+
+    let progress = progress::ProgressReporter::new();
+    if let Some(addr) = progress_addr {
+        progress::serve_on(addr, progress.clone());
+    }
+
+    let dataset_dir = Path::new(CODESEARCH_NET_DIR);
+    let evaluations_path = dataset_dir.join("evaluations.json");
+    let repos_dir = Path::new(EVAL_REPOS_DIR);
+    let db_path = Path::new(EVAL_DB_PATH);
+    let http_client = http_client::HttpClientWithProxy::new(None, None);
+    let api_key = std::env::var("OPENAI_API_KEY").unwrap();
 
     let evaluations = fs::read(&evaluations_path).expect("failed to read evaluations.json");
     let evaluations: Vec<EvaluationProject> = serde_json::from_slice(&evaluations).unwrap();
@@ -274,17 +525,18 @@ async fn run_evaluation(executor: &BackgroundExecutor, cx: &mut AsyncAppContext)
         api_key,
     ));
 
-    let language_registry = Arc::new(LanguageRegistry::new(Task::ready(()), executor.clone()));
-
-    cx.update(|cx| languages::init(language_registry.clone(), node_runtime.clone(), cx))
-        .unwrap();
-
     let mut semantic_index = SemanticIndex::new(db_path.into(), embedding_provider, cx)
         .await
         .unwrap();
 
+    let eval_db = EvalDb::open(Path::new(EVAL_DB_RESULTS_PATH))?;
+    let run_id = eval_db.start_run("text-embedding-3-small", &current_git_sha()?)?;
+
     let mut covered_result_count = 0;
     let mut total_result_count = 0;
+    let mut ndcg_sum = 0.;
+    let mut reciprocal_rank_sum = 0.;
+    let mut query_count = 0;
     eprint!("Running evals.");
 
     for evaluation_project in evaluations {
@@ -299,99 +551,194 @@ async fn run_evaluation(executor: &BackgroundExecutor, cx: &mut AsyncAppContext)
             continue;
         }
 
-        let project = cx
-            .update(|cx| {
-                Project::local(
-                    client.clone(),
-                    node_runtime.clone(),
-                    user_store.clone(),
-                    language_registry.clone(),
-                    fs.clone(),
-                    None,
-                    cx,
-                )
-            })
-            .unwrap();
+        let repo = evaluation_project.repo.clone();
+        let sha = evaluation_project.sha.clone();
+        let outcome = evaluate_project(&ctx, &mut semantic_index, evaluation_project, cx, &progress).await?;
 
-        let (worktree, _) = project
-            .update(cx, |project, cx| {
-                project.find_or_create_worktree(repo_dir, true, cx)
-            })?
-            .await?;
-
-        worktree
-            .update(cx, |worktree, _| {
-                worktree.as_local().unwrap().scan_complete()
-            })
-            .unwrap()
-            .await;
+        for query_results in &outcome.queries {
+            covered_result_count += query_results.covered_result_count;
+            total_result_count += query_results.total_result_count;
+            ndcg_sum += query_results.metrics.ndcg;
+            reciprocal_rank_sum += query_results.metrics.reciprocal_rank;
+            query_count += 1;
 
-        let project_index = cx
-            .update(|cx| semantic_index.project_index(project.clone(), cx))
-            .unwrap();
+            eval_db.record_query_outcome(run_id, &repo, &sha, query_results)?;
 
-        wait_for_indexing_complete(&project_index, cx).await;
+            println!("{}", serde_json::to_string(query_results).unwrap());
+        }
+    }
 
-        for query in evaluation_project.queries {
-            let results = cx
-                .update(|cx| {
-                    let project_index = project_index.read(cx);
-                    project_index.search(query.query.clone(), SEARCH_RESULT_LIMIT, cx)
-                })
-                .unwrap()
-                .await
-                .unwrap();
-
-            let results = SemanticIndex::load_results(results, &fs.clone(), &cx)
-                .await
-                .unwrap();
-
-            let mut project_covered_result_count = 0;
-            for expected_result in &query.expected_results {
-                let was_covered = results.iter().any(|result| {
-                    result.path.as_ref() == Path::new(&expected_result.file)
-                        && result.row_range.contains(&expected_result.lines.start)
-                        && result.row_range.contains(&expected_result.lines.end)
-                });
-                if was_covered {
-                    project_covered_result_count += 1
-                };
-            }
+    let mean_ndcg = if query_count == 0 {
+        0.
+    } else {
+        ndcg_sum / query_count as f64
+    };
+    let mrr = if query_count == 0 {
+        0.
+    } else {
+        reciprocal_rank_sum / query_count as f64
+    };
+    eval_db.finish_run(
+        run_id,
+        covered_result_count,
+        total_result_count,
+        mean_ndcg,
+        mrr,
+    )?;
+    progress.emit(progress::ProgressEvent::RunFinished {
+        covered_result_count,
+        total_result_count,
+        mean_ndcg,
+        mrr,
+    });
 
-            let query_results = EvaluationQueryOutcome {
-                query: query.query,
-                total_result_count: query.expected_results.len(),
-                covered_result_count: project_covered_result_count,
-                expected_results: query.expected_results,
-                actual_results: results
-                    .iter()
-                    .map(|result| EvaluationSearchResult {
-                        file: result.path.to_string_lossy().to_string(),
-                        lines: result.row_range.clone(),
-                    })
-                    .collect(),
-            };
+    eprint!(
+        "\rRan evals. {}/{} covered. NDCG@{}: {:.3}, MRR: {:.3}",
+        covered_result_count, total_result_count, SEARCH_RESULT_LIMIT, mean_ndcg, mrr
+    );
 
-            covered_result_count += query_results.covered_result_count;
-            total_result_count += query_results.total_result_count;
+    let mut regressed = false;
+    if let Some(baseline_run_id) = baseline.or(if compare_latest {
+        eval_db.latest_run_id(run_id)?
+    } else {
+        None
+    }) {
+        let comparison = eval_db.compare_runs(baseline_run_id, run_id)?;
+        eprintln!(
+            "\nCompared to run {}: {:.1}% -> {:.1}% coverage ({:+.1}%)",
+            comparison.baseline_run_id,
+            comparison.baseline_coverage() * 100.,
+            comparison.new_coverage() * 100.,
+            comparison.coverage_delta() * 100.,
+        );
+        for outcome in &comparison.newly_passed {
+            eprintln!(
+                "  NEW PASS   {} [{}]: {}",
+                outcome.repo, outcome.sha, outcome.query
+            );
+        }
+        for outcome in &comparison.newly_failed {
+            eprintln!(
+                "  REGRESSED  {} [{}]: {}",
+                outcome.repo, outcome.sha, outcome.query
+            );
+        }
+        regressed = comparison.has_regression();
 
-            println!("{}", serde_json::to_string(&query_results).unwrap());
+        if let Some(notify_config_path) = &notify_config {
+            let notify_config = notifier::NotifierConfig::load(notify_config_path)?;
+            let report = notifier::RegressionReport {
+                comparison: &comparison,
+                threshold: notify_config.threshold,
+            };
+            if report.has_regression() {
+                for notifier in notify_config.notifiers(Arc::new(http_client.clone())) {
+                    if let Err(err) = notifier.notify(&report).await {
+                        eprintln!("Error sending regression notification: {}", err);
+                    }
+                }
+            }
         }
     }
 
-    eprint!(
-        "\rRan evals. {}/{} covered.",
-        covered_result_count, total_result_count
-    );
+    Ok(regressed)
+}
+
+fn current_git_sha() -> Result<String> {
+    let output = Command::new("git").args(["rev-parse", "HEAD"]).output()?;
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// Starts an HTTP server that re-runs the eval suite for whichever repo a
+/// `push` webhook names, so quality can be tracked on every change to a
+/// watched repo instead of only on manual `Run` invocations.
+async fn serve(
+    executor: &BackgroundExecutor,
+    cx: &mut AsyncAppContext,
+    webhook_config_path: PathBuf,
+    addr: SocketAddr,
+) -> Result<()> {
+    let ctx = init_eval_context(executor, cx).await?;
+    let config = webhook::WebhookConfig::load(&webhook_config_path)?;
+
+    let dataset_dir = Path::new(CODESEARCH_NET_DIR);
+    let evaluations_path = dataset_dir.join("evaluations.json");
+    let db_path = Path::new(EVAL_DB_PATH);
+    let http_client = http_client::HttpClientWithProxy::new(None, None);
+    let api_key = std::env::var("OPENAI_API_KEY").unwrap();
+
+    let evaluations = fs::read(&evaluations_path).expect("failed to read evaluations.json");
+    let evaluations: Vec<EvaluationProject> = serde_json::from_slice(&evaluations).unwrap();
+
+    let embedding_provider = Arc::new(OpenAiEmbeddingProvider::new(
+        http_client.clone(),
+        OpenAiEmbeddingModel::TextEmbedding3Small,
+        open_ai::OPEN_AI_API_URL.to_string(),
+        api_key,
+    ));
+    let mut semantic_index = SemanticIndex::new(db_path.into(), embedding_provider, cx)
+        .await
+        .unwrap();
+
+    let (jobs_tx, mut jobs_rx) = futures::channel::mpsc::unbounded::<webhook::PushJob>();
+    let progress = progress::ProgressReporter::new();
+
+    eprintln!("Listening for GitHub webhooks on {addr}");
+    let app = webhook::router(config.pre_shared_keys, jobs_tx).merge(progress.router());
+    server::spawn_axum_server("webhook", addr, app);
+
+    while let Some(job) = jobs_rx.next().await {
+        let evaluation_project = evaluations
+            .iter()
+            .find(|project| project.repo == job.repo)
+            .cloned();
+        let response = match evaluation_project {
+            Some(mut evaluation_project) => {
+                evaluation_project.sha = job.sha.clone();
+                let repo_dir = ctx.repos_dir.join(&job.repo);
+                let fetched = fetch_eval_repo(
+                    job.repo.clone(),
+                    job.sha.clone(),
+                    &ctx.repos_dir,
+                    &http_client,
+                )
+                .await;
+                if !fetched || repo_dir.join(SKIP_EVAL_PATH).exists() {
+                    Err(format!(
+                        "failed to fetch {}@{}, skipping eval",
+                        job.repo, job.sha
+                    ))
+                } else {
+                    evaluate_project(&ctx, &mut semantic_index, evaluation_project, cx, &progress)
+                        .await
+                        .map(|outcome| serde_json::to_value(&outcome).unwrap())
+                        .map_err(|err| err.to_string())
+                }
+            }
+            None => Err(format!("{} is not a watched repo", job.repo)),
+        };
+        let _ = job.respond_to.send(response);
+    }
 
     Ok(())
 }
 
-async fn wait_for_indexing_complete(project_index: &Model<ProjectIndex>, cx: &mut AsyncAppContext) {
+async fn wait_for_indexing_complete(
+    project_index: &Model<ProjectIndex>,
+    cx: &mut AsyncAppContext,
+    progress: &progress::ProgressReporter,
+    repo: &str,
+) {
     let (tx, rx) = oneshot::channel();
     let mut tx = Some(tx);
+    let repo = repo.to_string();
+    let progress = progress.clone();
     let subscription = cx.update(|cx| {
         cx.subscribe(project_index, move |_, event, _| {
+            progress.emit(progress::ProgressEvent::IndexingStatusChanged {
+                repo: repo.clone(),
+                status: format!("{:?}", event),
+            });
             if let Status::Idle = event {
                 if let Some(tx) = tx.take() {
                     _ = tx.send(*event);
@@ -442,93 +789,149 @@ async fn fetch_eval_repos(
     Ok(())
 }
 
+/// Records the sha materialized into a repo's checkout directory by
+/// [`fetch_eval_repo`], so re-running `Fetch` can skip repos that are
+/// already up to date without shelling out to `git`.
+const FETCHED_SHA_PATH: &str = ".fetched_sha";
+
+/// Downloads `repo`@`sha` as a GitHub commit archive over `http_client` and
+/// unpacks it into `repos_dir`, rather than shelling out to `git`. This lets
+/// fetching scale with the async HTTP stack instead of spawning a git
+/// process per repo, and removes the hard dependency on a `git` binary.
+///
+/// Returns `true` if `repos_dir/repo` holds a checkout of `sha` ready for
+/// evaluation, and `false` if the repo should be skipped this run (a 404,
+/// a fetch/unpack failure, or an existing `.skip_eval` marker). Callers must
+/// not evaluate the checkout directory on `false`, since it may be empty or
+/// left over from a different sha.
 async fn fetch_eval_repo(
     repo: String,
     sha: String,
     repos_dir: &Path,
     http_client: &dyn HttpClient,
-) {
-    let Some((owner, repo_name)) = repo.split_once('/') else {
-        return;
+) -> bool {
+    let Some((_owner, repo_name)) = repo.split_once('/') else {
+        return false;
     };
-    let repo_dir = repos_dir.join(owner).join(repo_name);
+    let repo_dir = repos_dir.join(&repo);
     fs::create_dir_all(&repo_dir).unwrap();
+
     let skip_eval_path = repo_dir.join(SKIP_EVAL_PATH);
     if skip_eval_path.exists() {
-        return;
+        return false;
     }
-    if let Ok(head_content) = fs::read_to_string(&repo_dir.join(".git").join("HEAD")) {
-        if head_content.trim() == sha {
-            return;
+
+    let fetched_sha_path = repo_dir.join(FETCHED_SHA_PATH);
+    if let Ok(fetched_sha) = fs::read_to_string(&fetched_sha_path) {
+        if fetched_sha.trim() == sha {
+            return true;
         }
     }
-    let repo_response = http_client
-        .send(
-            http_client::Request::builder()
-                .method(Method::HEAD)
-                .uri(format!("https://github.com/{}", repo))
-                .body(Default::default())
-                .expect(""),
-        )
+
+    let archive_url = format!("https://github.com/{}/archive/{}.tar.gz", repo, sha);
+    let response = match http_client
+        .get(&archive_url, Default::default(), true)
         .await
-        .expect("failed to check github repo");
-    if !repo_response.status().is_success() && !repo_response.status().is_redirection() {
+    {
+        Ok(response) => response,
+        Err(err) => {
+            eprintln!("Failed to request archive for {repo}@{sha}: {err}");
+            return false;
+        }
+    };
+
+    if response.status().as_u16() == 404 {
         fs::write(&skip_eval_path, "").unwrap();
+        eprintln!("Repo {repo} is no longer public (404). Skipping");
+        return false;
+    } else if !response.status().is_success() {
         eprintln!(
-            "Repo {repo} is no longer public ({:?}). Skipping",
-            repo_response.status()
+            "Failed to fetch archive for {repo}@{sha}: {:?}",
+            response.status()
         );
-        return;
+        return false;
     }
-    if !repo_dir.join(".git").exists() {
-        let init_output = Command::new("git")
-            .current_dir(&repo_dir)
-            .args(&["init"])
-            .output()
-            .unwrap();
-        if !init_output.status.success() {
-            eprintln!(
-                "Failed to initialize git repository for {}: {}",
-                repo,
-                String::from_utf8_lossy(&init_output.stderr)
-            );
-            return;
-        }
+
+    let mut compressed = Vec::new();
+    if let Err(err) = response.into_body().read_to_end(&mut compressed).await {
+        eprintln!("Failed to read archive for {repo}@{sha}: {err}");
+        return false;
     }
-    let url = format!("https://github.com/{}.git", repo);
-    Command::new("git")
-        .current_dir(&repo_dir)
-        .args(&["remote", "add", "-f", "origin", &url])
-        .stdin(Stdio::null())
-        .output()
-        .unwrap();
-    let fetch_output = Command::new("git")
-        .current_dir(&repo_dir)
-        .args(&["fetch", "--depth", "1", "origin", &sha])
-        .stdin(Stdio::null())
-        .output()
-        .unwrap();
-    if !fetch_output.status.success() {
-        eprintln!(
-            "Failed to fetch {} for {}: {}",
-            sha,
-            repo,
-            String::from_utf8_lossy(&fetch_output.stderr)
-        );
-        return;
+
+    // Clear out anything left over from a previous checkout of this repo.
+    for entry in fs::read_dir(&repo_dir).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name() == FETCHED_SHA_PATH || entry.file_name() == SKIP_EVAL_PATH {
+            continue;
+        }
+        if entry.file_type().unwrap().is_dir() {
+            fs::remove_dir_all(entry.path()).unwrap();
+        } else {
+            fs::remove_file(entry.path()).unwrap();
+        }
     }
-    let checkout_output = Command::new("git")
-        .current_dir(&repo_dir)
-        .args(&["checkout", &sha])
-        .output()
-        .unwrap();
 
-    if !checkout_output.status.success() {
-        eprintln!(
-            "Failed to checkout {} for {}: {}",
-            sha,
-            repo,
-            String::from_utf8_lossy(&checkout_output.stderr)
-        );
+    // GitHub archives nest everything under a `{repo_name}-{sha}/` directory.
+    let archive_prefix = format!("{}-{}/", repo_name, sha);
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(compressed.as_slice()));
+    let entries = match archive.entries() {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Failed to read archive for {repo}@{sha}: {err}");
+            return false;
+        }
+    };
+    for entry in entries {
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                eprintln!("Failed to read tar entry for {repo}@{sha}: {err}");
+                return false;
+            }
+        };
+        let entry_path = match entry.path() {
+            Ok(path) => path.into_owned(),
+            Err(err) => {
+                eprintln!("Failed to read tar entry path for {repo}@{sha}: {err}");
+                return false;
+            }
+        };
+        let Ok(relative_path) = entry_path.strip_prefix(&archive_prefix) else {
+            continue;
+        };
+        if relative_path.as_os_str().is_empty() {
+            continue;
+        }
+
+        let destination = repo_dir.join(relative_path);
+        if entry.header().entry_type().is_dir() {
+            if let Err(err) = fs::create_dir_all(&destination) {
+                eprintln!(
+                    "Failed to create directory {} for {repo}@{sha}: {err}",
+                    destination.display()
+                );
+                return false;
+            }
+        } else {
+            if let Some(parent) = destination.parent() {
+                if let Err(err) = fs::create_dir_all(parent) {
+                    eprintln!(
+                        "Failed to create directory {} for {repo}@{sha}: {err}",
+                        parent.display()
+                    );
+                    return false;
+                }
+            }
+            if let Err(err) = entry.unpack(&destination) {
+                eprintln!(
+                    "Failed to unpack {} for {repo}@{sha}: {err}",
+                    destination.display()
+                );
+                return false;
+            }
+        }
     }
-}
\ No newline at end of file
+
+    fs::write(&fetched_sha_path, &sha).unwrap();
+    true
+}
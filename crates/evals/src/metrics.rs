@@ -0,0 +1,95 @@
+use crate::EvaluationSearchResult;
+
+/// Graded-relevance ranking metrics for a single query, computed from the
+/// CodeSearchNet annotation scores rather than a binary covered/uncovered
+/// count. `k` is the number of ranked results the metrics are computed over
+/// (the eval harness's search result limit).
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct QueryMetrics {
+    pub ndcg: f64,
+    pub reciprocal_rank: f64,
+    pub precision: f64,
+    pub recall: f64,
+}
+
+/// Computes NDCG@k, reciprocal rank, and precision/recall@k for a ranked list
+/// of `actual_results` against the graded `expected_results`.
+///
+/// Each actual result is assigned a gain equal to the relevance of the
+/// expected span it matches (0 if it matches none), and DCG@k is discounted
+/// by `log2(rank + 1)`. Each expected span can only be matched once, so two
+/// actual results that both overlap the same expected span don't double its
+/// contribution to DCG. NDCG is DCG divided by the ideal DCG obtained by
+/// sorting the expected relevances in descending order (0 if there are no
+/// expected results).
+pub fn compute(
+    expected_results: &[EvaluationSearchResult],
+    actual_results: &[EvaluationSearchResult],
+    k: usize,
+) -> QueryMetrics {
+    let mut matched_expected = vec![false; expected_results.len()];
+    let gains = actual_results
+        .iter()
+        .take(k)
+        .map(|actual| {
+            expected_results
+                .iter()
+                .enumerate()
+                .find(|(ix, expected)| !matched_expected[*ix] && matches(expected, actual))
+                .map_or(0, |(ix, expected)| {
+                    matched_expected[ix] = true;
+                    expected.relevance
+                })
+        })
+        .collect::<Vec<_>>();
+
+    let dcg: f64 = gains
+        .iter()
+        .enumerate()
+        .map(|(i, &gain)| gain as f64 / (i as f64 + 2.).log2())
+        .sum();
+
+    let mut ideal_relevances = expected_results
+        .iter()
+        .map(|expected| expected.relevance)
+        .collect::<Vec<_>>();
+    ideal_relevances.sort_unstable_by(|a, b| b.cmp(a));
+    let idcg: f64 = ideal_relevances
+        .iter()
+        .take(k)
+        .enumerate()
+        .map(|(i, &gain)| gain as f64 / (i as f64 + 2.).log2())
+        .sum();
+
+    let ndcg = if idcg == 0. { 0. } else { dcg / idcg };
+
+    let reciprocal_rank = gains
+        .iter()
+        .position(|&gain| gain > 0)
+        .map_or(0., |rank| 1. / (rank as f64 + 1.));
+
+    let relevant_in_top_k = gains.iter().filter(|&&gain| gain > 0).count();
+    let precision = if k == 0 {
+        0.
+    } else {
+        relevant_in_top_k as f64 / k as f64
+    };
+    let recall = if expected_results.is_empty() {
+        0.
+    } else {
+        relevant_in_top_k as f64 / expected_results.len() as f64
+    };
+
+    QueryMetrics {
+        ndcg,
+        reciprocal_rank,
+        precision,
+        recall,
+    }
+}
+
+fn matches(expected: &EvaluationSearchResult, actual: &EvaluationSearchResult) -> bool {
+    expected.file == actual.file
+        && actual.lines.contains(&expected.lines.start)
+        && actual.lines.contains(&expected.lines.end)
+}
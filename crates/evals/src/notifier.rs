@@ -0,0 +1,225 @@
+use crate::db::RunComparison;
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use http_client::{AsyncBody, HttpClient};
+use serde::Deserialize;
+use std::{path::Path, sync::Arc};
+
+/// Configuration for the regression notifier, loaded from a JSON or TOML
+/// file path passed to `Run --notify-config`. Either or both of `email`
+/// and `webhook` may be set; every configured channel is notified of a
+/// regression.
+#[derive(Clone, Deserialize)]
+pub struct NotifierConfig {
+    /// The fraction a metric (coverage, NDCG, or MRR) must drop by,
+    /// run-over-run, before a notification fires.
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+    pub email: Option<EmailConfig>,
+    pub webhook: Option<WebhookConfig>,
+}
+
+fn default_threshold() -> f64 {
+    0.05
+}
+
+#[derive(Clone, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+}
+
+impl NotifierConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read notifier config at {}", path.display()))?;
+        if path.extension().and_then(|ext| ext.to_str()) == Some("toml") {
+            toml::from_str(&content)
+                .with_context(|| format!("failed to parse notifier config at {}", path.display()))
+        } else {
+            serde_json::from_str(&content)
+                .with_context(|| format!("failed to parse notifier config at {}", path.display()))
+        }
+    }
+
+    /// Builds the `Notifier`s for whichever channels are configured.
+    pub fn notifiers(&self, http_client: Arc<dyn HttpClient>) -> Vec<Box<dyn Notifier>> {
+        let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+        if let Some(email) = &self.email {
+            notifiers.push(Box::new(EmailNotifier {
+                config: email.clone(),
+            }));
+        }
+        if let Some(webhook) = &self.webhook {
+            notifiers.push(Box::new(WebhookNotifier {
+                config: webhook.clone(),
+                http_client,
+            }));
+        }
+        notifiers
+    }
+}
+
+/// Everything needed to render an alert for one `run_evaluation` comparison.
+pub struct RegressionReport<'a> {
+    pub comparison: &'a RunComparison,
+    pub threshold: f64,
+}
+
+impl<'a> RegressionReport<'a> {
+    /// Whether the comparison actually warrants a notification: an
+    /// aggregate metric fell by more than `threshold`, or some repo's
+    /// coverage dropped to zero.
+    pub fn has_regression(&self) -> bool {
+        self.coverage_drop() > self.threshold
+            || self.ndcg_drop() > self.threshold
+            || self.mrr_drop() > self.threshold
+            || !self.comparison.zeroed_out_repos.is_empty()
+    }
+
+    fn coverage_drop(&self) -> f64 {
+        (self.comparison.baseline_coverage() - self.comparison.new_coverage()).max(0.)
+    }
+
+    fn ndcg_drop(&self) -> f64 {
+        (-self.comparison.ndcg_delta()).max(0.)
+    }
+
+    fn mrr_drop(&self) -> f64 {
+        (-self.comparison.mrr_delta()).max(0.)
+    }
+
+    pub fn subject(&self) -> String {
+        format!(
+            "Eval quality regression: run {} vs baseline {}",
+            self.comparison.new_run_id, self.comparison.baseline_run_id
+        )
+    }
+
+    pub fn body(&self) -> String {
+        let mut body = format!(
+            "Coverage: {:.1}% -> {:.1}% ({:+.1}%)\n\
+             NDCG: {:.3} -> {:.3} ({:+.3})\n\
+             MRR: {:.3} -> {:.3} ({:+.3})\n",
+            self.comparison.baseline_coverage() * 100.,
+            self.comparison.new_coverage() * 100.,
+            self.comparison.coverage_delta() * 100.,
+            self.comparison.baseline_mean_ndcg,
+            self.comparison.new_mean_ndcg,
+            self.comparison.ndcg_delta(),
+            self.comparison.baseline_mrr,
+            self.comparison.new_mrr,
+            self.comparison.mrr_delta(),
+        );
+
+        if !self.comparison.zeroed_out_repos.is_empty() {
+            body.push_str("\nRepos with zeroed-out coverage:\n");
+            for repo in &self.comparison.zeroed_out_repos {
+                body.push_str(&format!("  {}\n", repo.repo));
+            }
+        }
+
+        if !self.comparison.query_regressions.is_empty() {
+            body.push_str("\nPer-query regressions:\n");
+            for regression in &self.comparison.query_regressions {
+                body.push_str(&format!(
+                    "  {} [{}]: {:.1}% -> {:.1}%\n",
+                    regression.repo,
+                    regression.query,
+                    regression.baseline_coverage() * 100.,
+                    regression.new_coverage() * 100.,
+                ));
+            }
+        }
+
+        body
+    }
+}
+
+/// A single channel a regression alert can be dispatched over.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, report: &RegressionReport) -> Result<()>;
+}
+
+pub struct EmailNotifier {
+    config: EmailConfig,
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, report: &RegressionReport) -> Result<()> {
+        let config = self.config.clone();
+        let subject = report.subject();
+        let body = report.body();
+
+        smol::unblock(move || {
+            let mut builder = lettre::Message::builder()
+                .from(
+                    config
+                        .from
+                        .parse()
+                        .context("invalid notifier `from` address")?,
+                )
+                .subject(subject);
+            for to in &config.to {
+                builder = builder.to(to.parse().context("invalid notifier `to` address")?);
+            }
+            let email = builder
+                .body(body)
+                .context("failed to build notifier email")?;
+
+            let mailer = lettre::SmtpTransport::relay(&config.smtp_host)
+                .context("failed to configure notifier SMTP relay")?
+                .credentials(lettre::transport::smtp::authentication::Credentials::new(
+                    config.smtp_username.clone(),
+                    config.smtp_password.clone(),
+                ))
+                .build();
+            lettre::Transport::send(&mailer, &email).context("failed to send notifier email")?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub struct WebhookNotifier {
+    config: WebhookConfig,
+    http_client: Arc<dyn HttpClient>,
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, report: &RegressionReport) -> Result<()> {
+        let payload = serde_json::json!({
+            "subject": report.subject(),
+            "body": report.body(),
+            "baseline_run_id": report.comparison.baseline_run_id,
+            "new_run_id": report.comparison.new_run_id,
+            "coverage_delta": report.comparison.coverage_delta(),
+            "ndcg_delta": report.comparison.ndcg_delta(),
+            "mrr_delta": report.comparison.mrr_delta(),
+        });
+
+        let response = self
+            .http_client
+            .post_json(
+                &self.config.url,
+                AsyncBody::from(serde_json::to_vec(&payload)?),
+            )
+            .await
+            .context("failed to POST regression webhook")?;
+        if !response.status().is_success() {
+            anyhow::bail!("regression webhook returned {}", response.status());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use crate::server;
+use axum::{body::Body, response::Response, routing::get, Router};
+use futures::{channel::mpsc, SinkExt as _, StreamExt as _};
+use serde::Serialize;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// A single update emitted as `run_evaluation` progresses, mirroring what a
+/// human watching stderr would otherwise have to scrape off the
+/// `\r`-rewritten progress line.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    RepoStarted {
+        repo: String,
+    },
+    IndexingStatusChanged {
+        repo: String,
+        status: String,
+    },
+    QueryCompleted {
+        repo: String,
+        query: String,
+        covered_result_count: usize,
+        total_result_count: usize,
+    },
+    RunFinished {
+        covered_result_count: usize,
+        total_result_count: usize,
+        mean_ndcg: f64,
+        mrr: f64,
+    },
+}
+
+/// Fans `ProgressEvent`s out to every client currently connected to the
+/// `/progress` endpoint, as newline-delimited JSON. Each subscriber gets its
+/// own bounded channel, so a slow client misses the events emitted while its
+/// channel is full rather than blocking the eval run; only disconnected
+/// clients are removed.
+#[derive(Clone, Default)]
+pub struct ProgressReporter {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<ProgressEvent>>>>,
+}
+
+impl ProgressReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes `event` to every currently-connected subscriber.
+    pub fn emit(&self, event: ProgressEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain_mut(|tx| !matches!(tx.try_send(event.clone()), Err(err) if err.is_disconnected()));
+    }
+
+    fn subscribe(&self) -> mpsc::Receiver<ProgressEvent> {
+        let (tx, rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// The `/progress` route, ready to be mounted on or merged into an axum
+    /// `Router`.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/progress", get(stream_progress))
+            .with_state(self.clone())
+    }
+}
+
+async fn stream_progress(
+    axum::extract::State(reporter): axum::extract::State<ProgressReporter>,
+) -> Response {
+    let rx = reporter.subscribe();
+    let body = Body::from_stream(rx.map(|event| {
+        let mut line = serde_json::to_string(&event).expect("ProgressEvent always serializes");
+        line.push('\n');
+        Ok::<_, std::convert::Infallible>(line)
+    }));
+    Response::builder()
+        .header("content-type", "application/x-ndjson")
+        .body(body)
+        .expect("static headers always build a valid response")
+}
+
+/// Starts an HTTP server on `addr` exposing `reporter`'s `/progress` stream,
+/// so a browser or `curl` client can watch a long multi-repo run in real
+/// time without scraping stderr.
+pub fn serve_on(addr: SocketAddr, reporter: ProgressReporter) {
+    server::spawn_axum_server("progress", addr, reporter.router());
+    eprintln!("Streaming progress on http://{addr}/progress");
+}
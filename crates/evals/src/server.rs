@@ -0,0 +1,30 @@
+use axum::Router;
+use std::net::SocketAddr;
+
+/// Runs `app` to completion on a dedicated OS thread backed by its own
+/// single-threaded Tokio runtime.
+///
+/// `axum::serve` only accepts a Tokio listener (it's driven by a Tokio
+/// reactor under the hood), while the rest of the evaluator runs on gpui's
+/// smol-based `BackgroundExecutor`. Rather than mixing the two executors on
+/// one thread, `name`'s server gets its own Tokio runtime to live on.
+pub fn spawn_axum_server(name: &'static str, addr: SocketAddr, app: Router) {
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap_or_else(|err| panic!("failed to start a Tokio runtime for the {name} server: {err}"));
+        runtime.block_on(async move {
+            let listener = match tokio::net::TcpListener::bind(addr).await {
+                Ok(listener) => listener,
+                Err(err) => {
+                    eprintln!("failed to bind {name} server to {addr}: {err}");
+                    return;
+                }
+            };
+            if let Err(err) = axum::serve(listener, app).await {
+                eprintln!("{name} server exited: {err}");
+            }
+        });
+    });
+}
@@ -0,0 +1,149 @@
+use anyhow::{Context as _, Result};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use futures::channel::oneshot;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::{path::Path, sync::Arc};
+
+/// Pre-shared keys used to authenticate incoming GitHub webhook deliveries:
+/// a flat list of secrets, any one of which may have signed the payload (to
+/// allow rotation without downtime).
+#[derive(Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub pre_shared_keys: Vec<String>,
+}
+
+impl WebhookConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read webhook config at {}", path.display()))?;
+        Ok(serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse webhook config at {}", path.display()))?)
+    }
+}
+
+#[derive(Deserialize)]
+struct GithubPushEvent {
+    repository: GithubRepository,
+    head_commit: Option<GithubHeadCommit>,
+}
+
+#[derive(Deserialize)]
+struct GithubRepository {
+    full_name: String,
+}
+
+#[derive(Deserialize)]
+struct GithubHeadCommit {
+    id: String,
+}
+
+/// A push event that has been authenticated and parsed, awaiting a
+/// fetch+index+evaluate pass over `repo`@`sha`.
+pub struct PushJob {
+    pub repo: String,
+    pub sha: String,
+    pub respond_to: oneshot::Sender<Result<serde_json::Value, String>>,
+}
+
+#[derive(Clone)]
+struct WebhookState {
+    pre_shared_keys: Arc<Vec<String>>,
+    jobs_tx: futures::channel::mpsc::UnboundedSender<PushJob>,
+}
+
+pub fn router(
+    pre_shared_keys: Vec<String>,
+    jobs_tx: futures::channel::mpsc::UnboundedSender<PushJob>,
+) -> Router {
+    Router::new().route("/webhook", post(handle_webhook)).with_state(WebhookState {
+        pre_shared_keys: Arc::new(pre_shared_keys),
+        jobs_tx,
+    })
+}
+
+async fn handle_webhook(
+    State(state): State<WebhookState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let Some(signature) = headers
+        .get("X-Hub-Signature-256")
+        .and_then(|value| value.to_str().ok())
+    else {
+        return (StatusCode::UNAUTHORIZED, "missing X-Hub-Signature-256".to_string()).into_response();
+    };
+
+    if !verify_signature(&state.pre_shared_keys, &body, signature) {
+        return (StatusCode::UNAUTHORIZED, "invalid signature".to_string()).into_response();
+    }
+
+    let event: GithubPushEvent = match serde_json::from_slice(&body) {
+        Ok(event) => event,
+        Err(err) => {
+            return (StatusCode::BAD_REQUEST, format!("invalid push payload: {err}")).into_response()
+        }
+    };
+
+    let Some(head_commit) = event.head_commit else {
+        return (StatusCode::OK, "ignoring push with no head commit".to_string()).into_response();
+    };
+
+    let (tx, rx) = oneshot::channel();
+    let job = PushJob {
+        repo: event.repository.full_name,
+        sha: head_commit.id,
+        respond_to: tx,
+    };
+    if state.jobs_tx.unbounded_send(job).is_err() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "evaluator is not accepting jobs".to_string(),
+        )
+            .into_response();
+    }
+
+    match rx.await {
+        Ok(Ok(coverage)) => Json(coverage).into_response(),
+        Ok(Err(err)) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "evaluator dropped the job before responding".to_string(),
+        )
+            .into_response(),
+    }
+}
+
+/// Verifies `signature_header` (the `sha256=<hex>` value of the
+/// `X-Hub-Signature-256` header) against an HMAC-SHA256 of `body` computed
+/// with each configured pre-shared key, comparing in constant time.
+fn verify_signature(pre_shared_keys: &[String], body: &[u8], signature_header: &str) -> bool {
+    let Some(expected_hex) = signature_header.strip_prefix("sha256=") else {
+        return false;
+    };
+
+    pre_shared_keys.iter().any(|psk| {
+        let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(psk.as_bytes()) else {
+            return false;
+        };
+        mac.update(body);
+        let digest = mac.finalize().into_bytes();
+        let actual_hex = hex::encode(digest);
+        constant_time_eq(actual_hex.as_bytes(), expected_hex.as_bytes())
+    })
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}